@@ -1,6 +1,369 @@
 use crate::parser::{Literal, self};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use aes_gcm::{Aes256Gcm, Nonce as AesNonce, aead::Aead, aead::KeyInit};
+use argon2::Argon2;
+use chacha20poly1305::{ChaCha20Poly1305, Nonce as ChaChaNonce};
+use rand::{RngCore, rngs::OsRng};
+
+const MAGIC: &[u8] = b"orion";
+const CONTAINER_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Writes `value` as a LEB128 varint: 7 bits per byte, low bits first, with
+/// the high bit set on every byte but the last.
+fn write_varint(value: u64, out: &mut Vec<u8>) {
+    let mut value = value;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[derive(Debug)]
+pub enum DeserializeError {
+    UnexpectedEof,
+    BadMagic,
+    UnknownOpCode(u8),
+    InvalidLiteralTag(u8),
+    InvalidPatternTag(u8),
+    InvalidUtf8,
+    UnknownAlgorithm(u8),
+    MissingPassphrase,
+    DecryptionFailed,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnexpectedEof => write!(f, "unexpected end of input"),
+            Self::BadMagic => write!(f, "missing or corrupt `orion` magic header"),
+            Self::UnknownOpCode(tag) => write!(f, "unknown opcode tag {tag}"),
+            Self::InvalidLiteralTag(tag) => write!(f, "unknown literal tag {tag}"),
+            Self::InvalidPatternTag(tag) => write!(f, "unknown pattern tag {tag}"),
+            Self::InvalidUtf8 => write!(f, "string in the image is not valid UTF-8"),
+            Self::UnknownAlgorithm(tag) => write!(f, "unknown container algorithm tag {tag}"),
+            Self::MissingPassphrase => write!(f, "container is encrypted but no passphrase was given"),
+            Self::DecryptionFailed => write!(f, "failed to decrypt container (wrong passphrase or tampered data)"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported container version {version}"),
+        }
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+/// Walks a byte slice left to right, the read-side counterpart of the
+/// `Vec<u8>` that `serialize` builds up.
+pub struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn read_bytes(&mut self, n: usize) -> Result<&'a [u8], DeserializeError> {
+        let end = self.pos.checked_add(n).ok_or(DeserializeError::UnexpectedEof)?;
+        let slice = self.data.get(self.pos..end).ok_or(DeserializeError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, DeserializeError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, DeserializeError> {
+        let b = self.read_bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, DeserializeError> {
+        let b = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn read_i64(&mut self) -> Result<i64, DeserializeError> {
+        let b = self.read_bytes(8)?;
+        Ok(i64::from_be_bytes(b.try_into().unwrap()))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, DeserializeError> {
+        let b = self.read_bytes(8)?;
+        Ok(f64::from_bits(u64::from_be_bytes(b.try_into().unwrap())))
+    }
+
+    // Inverse of `write_varint`: accumulate 7 bits per byte into the result,
+    // shifting each byte's low 7 bits into position `7*i`, stopping at the
+    // first byte whose high bit is clear.
+    fn read_varint(&mut self) -> Result<u64, DeserializeError> {
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            result |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        Ok(result)
+    }
+
+    fn read_opcodes(&mut self, byte_len: usize) -> Result<Vec<OpCode>, DeserializeError> {
+        let end = self.pos.checked_add(byte_len).ok_or(DeserializeError::UnexpectedEof)?;
+        let mut instructions = vec![];
+        while self.pos < end {
+            instructions.push(OpCode::deserialize(self)?);
+        }
+        Ok(instructions)
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+}
+
+/// Minimal binary codec used to keep every struct/enum in this module in
+/// sync with its own (de)serialization, instead of each one open-coding it.
+pub trait Serialize {
+    fn write(&self, out: &mut Vec<u8>);
+}
+
+pub trait Deserialize: Sized {
+    fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError>;
+}
+
+impl Serialize for u8 {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+impl Deserialize for u8 {
+    fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+        cursor.read_u8()
+    }
+}
+
+impl Serialize for u16 {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.to_be_bytes());
+    }
+}
+impl Deserialize for u16 {
+    fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+        cursor.read_u16()
+    }
+}
+
+impl Serialize for String {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_varint(self.len() as u64, out); // Byte length, not char count
+        out.extend(self.as_bytes());
+    }
+}
+impl Deserialize for String {
+    fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+        let len = cursor.read_varint()?;
+        let bytes = cursor.read_bytes(len as usize)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DeserializeError::InvalidUtf8)
+    }
+}
+
+impl<T: Serialize> Serialize for Vec<T> {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_varint(self.len() as u64, out); // Length
+        self.iter().for_each(|item| item.write(out));
+    }
+}
+impl<T: Deserialize> Deserialize for Vec<T> {
+    fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+        let len = cursor.read_varint()?;
+        (0..len).map(|_| T::read(cursor)).collect()
+    }
+}
+
+impl Serialize for Literal {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(match self {
+            Literal::String(_) => 0,
+            Literal::Integer(_) => 1,
+            Literal::Single(_) => 2,
+        });
+        match self {
+            Literal::Integer(i) => out.extend(i.to_be_bytes()),
+            Literal::Single(f) => out.extend(f.to_bits().to_be_bytes()),
+            Literal::String(s) => s.write(out),
+        }
+    }
+}
+impl Deserialize for Literal {
+    fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+        Ok(match cursor.read_u8()? {
+            0 => Literal::String(String::read(cursor)?),
+            1 => Literal::Integer(cursor.read_i64()?),
+            2 => Literal::Single(cursor.read_f64()?),
+            other => return Err(DeserializeError::InvalidLiteralTag(other)),
+        })
+    }
+}
+
+impl Serialize for parser::Pattern {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Literal(lit) => {
+                out.push(0);
+                lit.write(out);
+            }
+            Self::Constructor(name, args) => {
+                out.push(1);
+                name.write(out);
+                args.write(out);
+            }
+            Self::Binding(name) => {
+                out.push(2);
+                name.write(out);
+            }
+            Self::Wildcard => out.push(3),
+        }
+    }
+}
+impl Deserialize for parser::Pattern {
+    fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+        Ok(match cursor.read_u8()? {
+            0 => Self::Literal(Literal::read(cursor)?),
+            1 => Self::Constructor(String::read(cursor)?, Vec::<parser::Pattern>::read(cursor)?),
+            2 => Self::Binding(String::read(cursor)?),
+            3 => Self::Wildcard,
+            other => return Err(DeserializeError::InvalidPatternTag(other)),
+        })
+    }
+}
+
+/// A u16 encoded as a LEB128 varint on the wire, as opposed to a plain
+/// `u16` which is fixed-width big-endian (e.g. `Chunk::reference` links).
+/// Used for `OpCode`'s operand fields via `binary_enum!` below.
+#[derive(Copy, Clone, Debug)]
+pub struct VarU16(u16);
+
+impl From<u16> for VarU16 {
+    fn from(v: u16) -> Self {
+        VarU16(v)
+    }
+}
+impl From<VarU16> for u16 {
+    fn from(v: VarU16) -> Self {
+        v.0
+    }
+}
+impl Serialize for VarU16 {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_varint(self.0 as u64, out);
+    }
+}
+impl Deserialize for VarU16 {
+    fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+        Ok(VarU16(cursor.read_varint()? as u16))
+    }
+}
+
+/// A sequence of opcodes framed by its serialized *byte* length rather than
+/// its element count, the layout `serialize` already uses for instruction
+/// streams so the decoder knows exactly where each one ends.
+#[derive(Clone, Debug)]
+pub struct OpBlock(Vec<OpCode>);
+
+impl From<Vec<OpCode>> for OpBlock {
+    fn from(v: Vec<OpCode>) -> Self {
+        OpBlock(v)
+    }
+}
+impl From<OpBlock> for Vec<OpCode> {
+    fn from(v: OpBlock) -> Self {
+        v.0
+    }
+}
+impl Serialize for OpBlock {
+    fn write(&self, out: &mut Vec<u8>) {
+        let mut serialized = vec![];
+        self.0.iter().for_each(|instr| instr.write(&mut serialized));
+        write_varint(serialized.len() as u64, out);
+        out.extend(serialized);
+    }
+}
+impl Deserialize for OpBlock {
+    fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+        let len = cursor.read_varint()?;
+        Ok(OpBlock(cursor.read_opcodes(len as usize)?))
+    }
+}
+
+// Generates `Serialize`/`Deserialize` for a tuple-variant enum whose wire
+// format is a one-byte tag followed by each field in order. `$store` is the
+// field's Rust type; `$wire` is the type that actually encodes/decodes it
+// (equal to `$store` unless the field needs an alternate wire
+// representation, e.g. `u16 as VarU16`).
+macro_rules! binary_enum {
+    (
+        $name:ident, $err:expr;
+        $( $tag:literal => $variant:ident( $( $field:ident : $store:ty as $wire:ty ),* ) ),* $(,)?
+    ) => {
+        impl Serialize for $name {
+            fn write(&self, out: &mut Vec<u8>) {
+                match self {
+                    $(
+                        Self::$variant( $( $field ),* ) => {
+                            out.push($tag);
+                            $( <$wire>::from(*$field).write(out); )*
+                        }
+                    )*
+                }
+            }
+        }
+
+        impl Deserialize for $name {
+            fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+                Ok(match cursor.read_u8()? {
+                    $( $tag => Self::$variant( $( <$store>::from(<$wire as Deserialize>::read(cursor)?) ),* ), )*
+                    other => return Err($err(other)),
+                })
+            }
+        }
+    };
+}
+
+// Generates `Serialize`/`Deserialize` for a struct whose wire format is
+// each field in declaration order (which must match the field order used
+// below). See `binary_enum!` above for the `$store`/`$wire` split.
+macro_rules! binary_struct {
+    ($name:ident { $( $field:ident : $store:ty as $wire:ty ),* $(,)? }) => {
+        impl Serialize for $name {
+            fn write(&self, out: &mut Vec<u8>) {
+                $( <$wire>::from(self.$field.clone()).write(out); )*
+            }
+        }
+
+        impl Deserialize for $name {
+            fn read(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+                Ok(Self {
+                    $( $field: <$store>::from(<$wire as Deserialize>::read(cursor)?), )*
+                })
+            }
+        }
+    };
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum OpCode {
     LoadConst(u16),        // (const_id)
@@ -12,55 +375,42 @@ pub enum OpCode {
     Constructor(u16, u16), // (constr_idx, to_eval)
     Tuple(u16),            // (amount)
 }
+
+binary_enum! {
+    OpCode, DeserializeError::UnknownOpCode;
+    0 => LoadConst(id: u16 as VarU16),
+    1 => LoadSym(id: u16 as VarU16),
+    2 => Call(argc: u16 as VarU16),
+    3 => Builtin(idx: u8 as u8, argc: u8 as u8),
+    4 => Def(id: u16 as VarU16, len: u16 as VarU16),
+    5 => Lambda(id: u16 as VarU16),
+    6 => Constructor(idx: u16 as VarU16, amount: u16 as VarU16),
+    7 => Tuple(amount: u16 as VarU16),
+}
+
 impl OpCode {
+    pub fn deserialize(cursor: &mut Cursor) -> Result<Self, DeserializeError> {
+        Deserialize::read(cursor)
+    }
+
     pub fn serialize(&self) -> Vec<u8> {
-        match self {
-            Self::LoadConst(id) => {
-                let mut to_ret = vec![0];
-                to_ret.extend(&id.to_be_bytes());
-                to_ret
-            }
-            Self::LoadSym(id) => {
-                let mut to_ret = vec![1];
-                to_ret.extend(&id.to_be_bytes());
-                to_ret
-            }
-            Self::Call(argc) => {
-                let mut to_ret = vec![2];
-                to_ret.extend(&argc.to_be_bytes());
-                to_ret
-            }
-            Self::Builtin(idx, argc) => vec![3, *idx, *argc],
-            Self::Def(id, len) => {
-                let mut to_ret = vec![4];
-                to_ret.extend(&id.to_be_bytes());
-                to_ret.extend(&len.to_be_bytes());
-                to_ret
-            }
-            Self::Lambda(id) => {
-                let mut to_ret = vec![5];
-                to_ret.extend(&id.to_be_bytes());
-                to_ret
-            }
-            Self::Constructor(idx, amount) => {
-                let mut to_ret = vec![6];
-                to_ret.extend(&idx.to_be_bytes());
-                to_ret.extend(&amount.to_be_bytes());
-                to_ret
-            }
-            Self::Tuple(amount) => {
-                let mut to_ret = vec![7];
-                to_ret.extend(&amount.to_be_bytes());
-                to_ret
-            }
-        }
+        let mut to_ret = vec![];
+        Serialize::write(self, &mut to_ret);
+        to_ret
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct Chunk {
-    pub instructions: Vec<OpCode>,
     pub reference: Vec<u16>,
+    pub instructions: Vec<OpCode>,
+}
+
+binary_struct! {
+    Chunk {
+        reference: Vec<u16> as Vec<u16>,
+        instructions: Vec<OpCode> as OpBlock,
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -68,19 +418,77 @@ pub struct Pattern {
     pub pat: parser::Pattern,
     pub to_exec: Vec<OpCode>,
 }
+
+binary_struct! {
+    Pattern {
+        pat: parser::Pattern as parser::Pattern,
+        to_exec: Vec<OpCode> as OpBlock,
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Match {
     pub expression: Vec<OpCode>,
     pub patterns: Vec<Pattern>,
 }
+
+binary_struct! {
+    Match {
+        expression: Vec<OpCode> as OpBlock,
+        patterns: Vec<Pattern> as Vec<Pattern>,
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Bytecode {
-    pub chunks: Vec<Chunk>,
-    pub matches: Vec<Match>,
     pub symbols: Vec<String>,
     pub constants: Vec<Literal>,
-    pub instructions: Vec<OpCode>,
     pub constructors: Vec<u8>,
+    pub chunks: Vec<Chunk>,
+    pub instructions: Vec<OpCode>,
+    pub matches: Vec<Match>,
+}
+
+binary_struct! {
+    Bytecode {
+        symbols: Vec<String> as Vec<String>,
+        constants: Vec<Literal> as Vec<Literal>,
+        constructors: Vec<u8> as Vec<u8>,
+        chunks: Vec<Chunk> as Vec<Chunk>,
+        instructions: Vec<OpCode> as OpBlock,
+        matches: Vec<Match> as Vec<Match>,
+    }
+}
+
+/// Selects how `Bytecode::serialize_container` protects the payload it
+/// wraps. `Plaintext` (tag 0) involves no key material: the container just
+/// carries the plain `serialize` image.
+#[derive(Copy, Clone, Debug)]
+pub enum Algorithm {
+    Plaintext = 0,
+    Aes256Gcm = 1,
+    ChaCha20Poly1305 = 2,
+}
+
+impl Algorithm {
+    fn from_tag(tag: u8) -> Result<Self, DeserializeError> {
+        match tag {
+            0 => Ok(Self::Plaintext),
+            1 => Ok(Self::Aes256Gcm),
+            2 => Ok(Self::ChaCha20Poly1305),
+            other => Err(DeserializeError::UnknownAlgorithm(other)),
+        }
+    }
+}
+
+// Derives a 256-bit AEAD key from a passphrase and salt with Argon2's
+// recommended defaults.
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .expect("argon2 key derivation failed");
+    key
 }
 
 impl Bytecode {
@@ -94,66 +502,669 @@ impl Bytecode {
             matches: vec![],
         }
     }
+
     // All numbers here are big endian
+    // except lengths, counts and opcode operands, which are LEB128 varints
     pub fn serialize(&self) -> Vec<u8> {
-        let mut to_ret = "orion".chars().into_iter().map(|c| c as u8).collect::<Vec<u8>>(); // Magic value
+        let mut to_ret = MAGIC.to_vec(); // Magic value
         to_ret.extend_from_slice(&(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32).to_be_bytes()); // Timestamp
-        
-        // Symbols
-        to_ret.extend(&(self.symbols.len() as u16).to_be_bytes()); // Length
-        self.symbols.iter().for_each(|sym| {
-            sym.chars().for_each(|c| to_ret.push(c as u8));
-            to_ret.push(0); // Mark termination
-        });
+        Serialize::write(self, &mut to_ret);
+        to_ret
+    }
 
-        // Consts
-        to_ret.extend(&(self.constants.len() as u16).to_be_bytes()); // Length
-        self.constants.iter().for_each(|c| {
-            to_ret.push(match c {
-                Literal::String(_) => 0,
-                Literal::Integer(_) => 1,
-                Literal::Single(_) => 2,
-            });
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        let mut cursor = Cursor::new(bytes);
+
+        if cursor.read_bytes(MAGIC.len())? != MAGIC {
+            return Err(DeserializeError::BadMagic);
+        }
+        let _timestamp = cursor.read_u32()?;
+
+        Deserialize::read(&mut cursor)
+    }
+
+    /// Wraps `serialize`'s output in a `magic || version || alg_tag ||
+    /// salt || nonce || ciphertext+tag` container. `Algorithm::Plaintext`
+    /// needs no `passphrase` and leaves the payload readable: it writes no
+    /// salt, nonce or tag, only `magic || version || alg_tag || payload`; the other
+    /// variants authenticate-encrypt it with a key derived from
+    /// `passphrase` via Argon2. Returns `Err(DeserializeError::MissingPassphrase)`
+    /// if an encrypted algorithm is chosen without one, mirroring how
+    /// `deserialize_container` handles the read side.
+    pub fn serialize_container(&self, algorithm: Algorithm, passphrase: Option<&str>) -> Result<Vec<u8>, DeserializeError> {
+        let payload = self.serialize();
+
+        let mut to_ret = MAGIC.to_vec();
+        to_ret.push(CONTAINER_VERSION);
+        to_ret.push(algorithm as u8);
+
+        match algorithm {
+            Algorithm::Plaintext => to_ret.extend(payload),
+            Algorithm::Aes256Gcm | Algorithm::ChaCha20Poly1305 => {
+                let passphrase = passphrase.ok_or(DeserializeError::MissingPassphrase)?;
+
+                let mut salt = [0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                let mut nonce = [0u8; NONCE_LEN];
+                OsRng.fill_bytes(&mut nonce);
+                let key = derive_key(passphrase, &salt);
+
+                let ciphertext = match algorithm {
+                    Algorithm::Aes256Gcm => Aes256Gcm::new_from_slice(&key)
+                        .expect("key is exactly 32 bytes")
+                        .encrypt(AesNonce::from_slice(&nonce), payload.as_slice())
+                        .expect("encryption does not fail"),
+                    Algorithm::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(&key)
+                        .expect("key is exactly 32 bytes")
+                        .encrypt(ChaChaNonce::from_slice(&nonce), payload.as_slice())
+                        .expect("encryption does not fail"),
+                    Algorithm::Plaintext => unreachable!(),
+                };
+
+                to_ret.extend(salt);
+                to_ret.extend(nonce);
+                to_ret.extend(ciphertext);
+            }
+        }
+
+        Ok(to_ret)
+    }
+
+    /// Inverse of `serialize_container`. `passphrase` is only consulted
+    /// (and required) when the container's `alg_tag` says it's encrypted.
+    pub fn deserialize_container(bytes: &[u8], passphrase: Option<&str>) -> Result<Self, DeserializeError> {
+        let mut cursor = Cursor::new(bytes);
+
+        if cursor.read_bytes(MAGIC.len())? != MAGIC {
+            return Err(DeserializeError::BadMagic);
+        }
+        let version = cursor.read_u8()?;
+        if version != CONTAINER_VERSION {
+            return Err(DeserializeError::UnsupportedVersion(version));
+        }
+        let algorithm = Algorithm::from_tag(cursor.read_u8()?)?;
+
+        let payload = match algorithm {
+            Algorithm::Plaintext => cursor.read_bytes(cursor.remaining())?.to_vec(),
+            Algorithm::Aes256Gcm | Algorithm::ChaCha20Poly1305 => {
+                let passphrase = passphrase.ok_or(DeserializeError::MissingPassphrase)?;
+
+                let salt = cursor.read_bytes(SALT_LEN)?;
+                let nonce = cursor.read_bytes(NONCE_LEN)?;
+                let ciphertext = cursor.read_bytes(cursor.remaining())?;
+                let key = derive_key(passphrase, salt);
 
-            to_ret.extend(match c {
-                Literal::Integer(i) => i.to_be_bytes().to_vec(),
-                Literal::Single(f) => f.to_bits().to_be_bytes().to_vec(),
-                Literal::String(s) => {
-                    let mut to_ex = s.chars().map(|c| c as u8).collect::<Vec<_>>();
-                    to_ex.push(0); // Mark termination
-                    to_ex
+                match algorithm {
+                    Algorithm::Aes256Gcm => Aes256Gcm::new_from_slice(&key)
+                        .map_err(|_| DeserializeError::DecryptionFailed)?
+                        .decrypt(AesNonce::from_slice(nonce), ciphertext)
+                        .map_err(|_| DeserializeError::DecryptionFailed)?,
+                    Algorithm::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(&key)
+                        .map_err(|_| DeserializeError::DecryptionFailed)?
+                        .decrypt(ChaChaNonce::from_slice(nonce), ciphertext)
+                        .map_err(|_| DeserializeError::DecryptionFailed)?,
+                    Algorithm::Plaintext => unreachable!(),
                 }
-            })
+            }
+        };
+
+        Self::deserialize(&payload)
+    }
+
+    /// Renders a human-readable, line-oriented listing of this program:
+    /// the symbol table, constant pool, constructors, then every
+    /// chunk/top-level/match instruction stream as mnemonics, with symbol
+    /// references resolved to `@name` where possible. `assemble` is the
+    /// inverse.
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+
+        self.symbols.iter().enumerate().for_each(|(id, name)| {
+            out.push_str(&format!("sym {id} {name}\n"));
         });
 
-        // Constructors
-        to_ret.extend(&(self.constructors.len() as u16).to_be_bytes());
-        to_ret.extend(self.constructors.clone());
+        self.constants.iter().enumerate().for_each(|(id, c)| {
+            out.push_str(&format!("const {id} {}\n", render_literal(c)));
+        });
+
+        self.constructors.iter().enumerate().for_each(|(id, byte)| {
+            out.push_str(&format!("ctor {id} {byte}\n"));
+        });
 
-        // Chunks
-        to_ret.extend(&(self.chunks.len() as u16).to_be_bytes());
-        self.chunks.iter().for_each(|chunk| {
-            to_ret.extend(&(chunk.reference.len() as u16).to_be_bytes());
-            chunk.reference.iter().for_each(|link| {
-                to_ret.extend(&link.to_be_bytes());
+        self.chunks.iter().enumerate().for_each(|(id, chunk)| {
+            let refs = chunk.reference.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(" ");
+            out.push_str(&format!("chunk {id} ref {refs}\n"));
+            chunk.instructions.iter().for_each(|instr| {
+                out.push_str(&format!("chunk {id} op {}\n", self.render_opcode(instr)));
             });
+        });
 
-            let serialized = chunk.instructions.iter().map(|instr| {
-                instr.serialize()
-            }).flatten();
-            to_ret.extend(&(serialized.clone().count() as u16).to_be_bytes());
-            to_ret.extend(serialized)
+        self.instructions.iter().for_each(|instr| {
+            out.push_str(&format!("op {}\n", self.render_opcode(instr)));
         });
 
-        // Instructions
-        let serialized = self.instructions.iter().map(|instr| {
-            instr.serialize()
-        }).flatten();
-        to_ret.extend(&(serialized.clone().count() as u16).to_be_bytes());
-        to_ret.extend(serialized);
+        self.matches.iter().enumerate().for_each(|(id, m)| {
+            out.push_str(&format!("match {id}\n"));
+            m.expression.iter().for_each(|instr| {
+                out.push_str(&format!("match {id} expr {}\n", self.render_opcode(instr)));
+            });
+            m.patterns.iter().enumerate().for_each(|(pid, pattern)| {
+                out.push_str(&format!("match {id} pat {pid} {}\n", render_pattern(&pattern.pat)));
+                pattern.to_exec.iter().for_each(|instr| {
+                    out.push_str(&format!("match {id} patop {pid} {}\n", self.render_opcode(instr)));
+                });
+            });
+        });
 
-        // Match
-        
-        to_ret
+        out
+    }
+
+    fn render_opcode(&self, instr: &OpCode) -> String {
+        // `@name` is only unambiguous (and thus only safe to round-trip
+        // through `assemble`) when no other symbol shares that name;
+        // duplicates fall back to the numeric id.
+        let sym = |id: u16| match self.symbols.get(id as usize) {
+            Some(name) if self.symbols.iter().filter(|other| *other == name).count() == 1 => {
+                format!("@{name}")
+            }
+            _ => id.to_string(),
+        };
+        match instr {
+            OpCode::LoadConst(id) => format!("load_const {id}"),
+            OpCode::LoadSym(id) => format!("load_sym {}", sym(*id)),
+            OpCode::Call(argc) => format!("call {argc}"),
+            OpCode::Builtin(idx, argc) => format!("builtin {idx} {argc}"),
+            OpCode::Def(id, len) => format!("def {} {len}", sym(*id)),
+            OpCode::Lambda(id) => format!("lambda {id}"),
+            OpCode::Constructor(idx, to_eval) => format!("constructor {idx} {to_eval}"),
+            OpCode::Tuple(amount) => format!("tuple {amount}"),
+        }
+    }
+
+    /// Parses the textual listing `disassemble` produces back into a
+    /// `Bytecode`. `assemble(disassemble(b))` reproduces a `Bytecode`
+    /// equivalent to `b`, except that symbol names and the names in binding
+    /// and constructor patterns are written unquoted: an empty name, or one
+    /// containing whitespace (or, in a pattern, `(`, `)` or `,`), is not
+    /// read back as written.
+    pub fn assemble(text: &str) -> Result<Self, AssembleError> {
+        // A match's patterns, keyed by pattern id, paired with the pattern
+        // text (absent until its `pat` line is seen) and its `to_exec`
+        // opcodes (accumulated as `patop` lines are seen).
+        type PatternsById = BTreeMap<u16, (Option<parser::Pattern>, Vec<OpCode>)>;
+
+        // Symbols first, so later lines can resolve `@name` operands.
+        let mut symbols: BTreeMap<u16, String> = BTreeMap::new();
+        text.lines().map(str::trim).filter(|l| !l.is_empty()).try_for_each(|line| {
+            if let ["sym", id, name] = line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                symbols.insert(parse_id(id)?, name.to_string());
+            }
+            Ok::<_, AssembleError>(())
+        })?;
+        // A name shared by more than one symbol can't be resolved back to a
+        // single id, so it's excluded here; `disassemble` never emits such a
+        // name as `@name` in the first place (see `render_opcode`), and a
+        // hand-written listing that uses one anyway surfaces as an
+        // `UnknownSymbol` error below rather than silently picking one id.
+        let mut name_counts: HashMap<&str, usize> = HashMap::new();
+        symbols.values().for_each(|name| *name_counts.entry(name.as_str()).or_insert(0) += 1);
+        let symbols_by_name: HashMap<String, u16> = symbols
+            .iter()
+            .filter(|(_, name)| name_counts[name.as_str()] == 1)
+            .map(|(id, name)| (name.clone(), *id))
+            .collect();
+
+        let mut constants: BTreeMap<u16, Literal> = BTreeMap::new();
+        let mut constructors: BTreeMap<u16, u8> = BTreeMap::new();
+        let mut chunk_refs: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+        let mut chunk_ops: BTreeMap<u16, Vec<OpCode>> = BTreeMap::new();
+        let mut top_ops = vec![];
+        let mut match_expr: BTreeMap<u16, Vec<OpCode>> = BTreeMap::new();
+        let mut match_pats: BTreeMap<u16, PatternsById> = BTreeMap::new();
+
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+            match tokens.as_slice() {
+                ["sym", ..] => {} // Already consumed above
+                ["const", id, ..] => {
+                    let rest = line.splitn(3, char::is_whitespace).nth(2).unwrap_or("").trim();
+                    constants.insert(parse_id(id)?, parse_literal(rest)?);
+                }
+                ["ctor", id, byte] => {
+                    constructors.insert(parse_id(id)?, byte.parse().map_err(|_| AssembleError::BadOperand(byte.to_string()))?);
+                }
+                ["chunk", id, "ref", rest @ ..] => {
+                    let refs = rest.iter().map(|r| parse_id(r)).collect::<Result<Vec<_>, _>>()?;
+                    chunk_refs.insert(parse_id(id)?, refs);
+                }
+                ["chunk", id, "op", rest @ ..] => {
+                    chunk_ops.entry(parse_id(id)?).or_default().push(parse_opcode(rest, &symbols_by_name)?);
+                }
+                ["op", rest @ ..] => {
+                    top_ops.push(parse_opcode(rest, &symbols_by_name)?);
+                }
+                ["match", id] => {
+                    // Unconditional anchor line so a match with an empty
+                    // expression and no patterns still round-trips.
+                    match_expr.entry(parse_id(id)?).or_default();
+                }
+                ["match", id, "expr", rest @ ..] => {
+                    match_expr.entry(parse_id(id)?).or_default().push(parse_opcode(rest, &symbols_by_name)?);
+                }
+                ["match", id, "pat", pid, ..] => {
+                    let rest = line.splitn(5, char::is_whitespace).nth(4).unwrap_or("").trim();
+                    let entry = match_pats.entry(parse_id(id)?).or_default().entry(parse_id(pid)?).or_insert((None, vec![]));
+                    entry.0 = Some(parse_pattern(rest)?);
+                }
+                ["match", id, "patop", pid, rest @ ..] => {
+                    let entry = match_pats.entry(parse_id(id)?).or_default().entry(parse_id(pid)?).or_insert((None, vec![]));
+                    entry.1.push(parse_opcode(rest, &symbols_by_name)?);
+                }
+                _ => return Err(AssembleError::UnexpectedLine(line.to_string())),
+            }
+        }
+
+        let chunks = match chunk_refs.keys().chain(chunk_ops.keys()).max().copied() {
+            None => vec![],
+            Some(max_id) => (0..=max_id)
+                .map(|id| Chunk {
+                    reference: chunk_refs.remove(&id).unwrap_or_default(),
+                    instructions: chunk_ops.remove(&id).unwrap_or_default(),
+                })
+                .collect(),
+        };
+
+        let matches = match match_expr.keys().chain(match_pats.keys()).max().copied() {
+            None => vec![],
+            Some(max_id) => (0..=max_id)
+                .map(|id| {
+                    let expression = match_expr.remove(&id).unwrap_or_default();
+                    let patterns = match match_pats.remove(&id) {
+                        None => vec![],
+                        Some(mut pats) => {
+                            let max_pid = pats.keys().max().copied().unwrap();
+                            (0..=max_pid)
+                                .map(|pid| {
+                                    let (pat, to_exec) = pats.remove(&pid).unwrap_or((None, vec![]));
+                                    Ok(Pattern {
+                                        pat: pat.ok_or(AssembleError::MissingPattern(id, pid))?,
+                                        to_exec,
+                                    })
+                                })
+                                .collect::<Result<Vec<_>, AssembleError>>()?
+                        }
+                    };
+                    Ok(Match { expression, patterns })
+                })
+                .collect::<Result<Vec<_>, AssembleError>>()?,
+        };
+
+        Ok(Self {
+            symbols: into_dense_vec(symbols, "sym")?,
+            constants: into_dense_vec(constants, "const")?,
+            constructors: into_dense_vec(constructors, "ctor")?,
+            chunks,
+            instructions: top_ops,
+            matches,
+        })
+    }
+}
+
+/// Converts an id -> value map into a vec indexed by id, the way `symbols`,
+/// `constants`, and `constructors` are stored. Unlike `chunks`/`matches`
+/// (which default a missing id to an empty value), a gap here would
+/// silently shift every later id out from under whatever references it by
+/// index, so it's rejected instead of filled in.
+fn into_dense_vec<T>(mut map: BTreeMap<u16, T>, kind: &'static str) -> Result<Vec<T>, AssembleError> {
+    let Some(max_id) = map.keys().max().copied() else {
+        return Ok(vec![]);
+    };
+    (0..=max_id).map(|id| map.remove(&id).ok_or(AssembleError::MissingId(kind, id))).collect()
+}
+
+#[derive(Debug)]
+pub enum AssembleError {
+    UnexpectedLine(String),
+    BadOperand(String),
+    UnknownSymbol(String),
+    UnknownMnemonic(String),
+    BadLiteral(String),
+    BadPattern(String),
+    MissingPattern(u16, u16),
+    MissingId(&'static str, u16),
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnexpectedLine(line) => write!(f, "unexpected line: {line}"),
+            Self::BadOperand(tok) => write!(f, "bad operand: {tok}"),
+            Self::UnknownSymbol(name) => write!(f, "reference to unknown symbol @{name}"),
+            Self::UnknownMnemonic(mnemonic) => write!(f, "unknown mnemonic: {mnemonic}"),
+            Self::BadLiteral(tok) => write!(f, "bad literal: {tok}"),
+            Self::BadPattern(tok) => write!(f, "bad pattern: {tok}"),
+            Self::MissingPattern(m, p) => write!(f, "match {m} pattern {p} has no `pat` line"),
+            Self::MissingId(kind, id) => write!(f, "{kind} ids must be contiguous from 0, but {id} is missing"),
+        }
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+fn parse_id(tok: &str) -> Result<u16, AssembleError> {
+    tok.parse().map_err(|_| AssembleError::BadOperand(tok.to_string()))
+}
+
+fn render_literal(lit: &Literal) -> String {
+    match lit {
+        Literal::Integer(v) => format!("int {v}"),
+        Literal::Single(v) => format!("float {v}"),
+        Literal::String(s) => format!("str {}", quote(s)),
+    }
+}
+
+fn parse_literal(text: &str) -> Result<Literal, AssembleError> {
+    let (tag, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let rest = rest.trim();
+    Ok(match tag {
+        "int" => Literal::Integer(rest.parse().map_err(|_| AssembleError::BadLiteral(text.to_string()))?),
+        "float" => Literal::Single(rest.parse().map_err(|_| AssembleError::BadLiteral(text.to_string()))?),
+        "str" => Literal::String(unquote(rest)?),
+        other => return Err(AssembleError::BadLiteral(other.to_string())),
+    })
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    s.chars().for_each(|c| match c {
+        '"' => out.push_str("\\\""),
+        '\\' => out.push_str("\\\\"),
+        '\n' => out.push_str("\\n"),
+        other => out.push(other),
+    });
+    out.push('"');
+    out
+}
+
+fn unquote(text: &str) -> Result<String, AssembleError> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| AssembleError::BadLiteral(text.to_string()))?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => return Err(AssembleError::BadLiteral(text.to_string())),
+        }
+    }
+    Ok(out)
+}
+
+fn parse_opcode(tokens: &[&str], symbols_by_name: &HashMap<String, u16>) -> Result<OpCode, AssembleError> {
+    let num = |tok: &str| -> Result<u16, AssembleError> { parse_id(tok) };
+    let sym = |tok: &str| -> Result<u16, AssembleError> {
+        match tok.strip_prefix('@') {
+            Some(name) => symbols_by_name.get(name).copied().ok_or_else(|| AssembleError::UnknownSymbol(name.to_string())),
+            None => num(tok),
+        }
+    };
+    match tokens {
+        ["load_const", id] => Ok(OpCode::LoadConst(num(id)?)),
+        ["load_sym", id] => Ok(OpCode::LoadSym(sym(id)?)),
+        ["call", argc] => Ok(OpCode::Call(num(argc)?)),
+        ["builtin", idx, argc] => Ok(OpCode::Builtin(
+            idx.parse().map_err(|_| AssembleError::BadOperand(idx.to_string()))?,
+            argc.parse().map_err(|_| AssembleError::BadOperand(argc.to_string()))?,
+        )),
+        ["def", id, len] => Ok(OpCode::Def(sym(id)?, num(len)?)),
+        ["lambda", id] => Ok(OpCode::Lambda(num(id)?)),
+        ["constructor", idx, to_eval] => Ok(OpCode::Constructor(num(idx)?, num(to_eval)?)),
+        ["tuple", amount] => Ok(OpCode::Tuple(num(amount)?)),
+        other => Err(AssembleError::UnknownMnemonic(other.join(" "))),
+    }
+}
+
+fn render_pattern(pat: &parser::Pattern) -> String {
+    match pat {
+        parser::Pattern::Literal(lit) => render_literal(lit),
+        parser::Pattern::Constructor(name, args) => {
+            let args = args.iter().map(render_pattern).collect::<Vec<_>>().join(", ");
+            format!("ctor {name}({args})")
+        }
+        parser::Pattern::Binding(name) => format!("bind {name}"),
+        parser::Pattern::Wildcard => "_".to_string(),
+    }
+}
+
+fn parse_pattern(text: &str) -> Result<parser::Pattern, AssembleError> {
+    let mut chars = text.chars().peekable();
+    parse_pattern_from(&mut chars)
+}
+
+fn parse_pattern_from(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<parser::Pattern, AssembleError> {
+    let skip_ws = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        while matches!(chars.peek(), Some(c) if c.is_whitespace()) {
+            chars.next();
+        }
+    };
+    let take_word = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        skip_ws(chars);
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '(' || c == ')' || c == ',' {
+                break;
+            }
+            word.push(c);
+            chars.next();
+        }
+        word
+    };
+
+    skip_ws(chars);
+    let tag = take_word(chars);
+    Ok(match tag.as_str() {
+        "_" => parser::Pattern::Wildcard,
+        "bind" => parser::Pattern::Binding(take_word(chars)),
+        "int" => parser::Pattern::Literal(Literal::Integer(
+            take_word(chars).parse().map_err(|_| AssembleError::BadPattern(tag.clone()))?,
+        )),
+        "float" => parser::Pattern::Literal(Literal::Single(
+            take_word(chars).parse().map_err(|_| AssembleError::BadPattern(tag.clone()))?,
+        )),
+        "str" => {
+            skip_ws(chars);
+            if chars.next() != Some('"') {
+                return Err(AssembleError::BadPattern(tag));
+            }
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('\\') => match chars.next() {
+                        Some('"') => s.push('"'),
+                        Some('\\') => s.push('\\'),
+                        Some('n') => s.push('\n'),
+                        Some(other) => s.push(other),
+                        None => return Err(AssembleError::BadPattern(s)),
+                    },
+                    Some('"') => break,
+                    Some(other) => s.push(other),
+                    None => return Err(AssembleError::BadPattern(s)),
+                }
+            }
+            parser::Pattern::Literal(Literal::String(s))
+        }
+        "ctor" => {
+            let name = take_word(chars);
+            skip_ws(chars);
+            let mut args = vec![];
+            if chars.peek() == Some(&'(') {
+                chars.next();
+                loop {
+                    skip_ws(chars);
+                    if chars.peek() == Some(&')') {
+                        chars.next();
+                        break;
+                    }
+                    args.push(parse_pattern_from(chars)?);
+                    skip_ws(chars);
+                    match chars.next() {
+                        Some(',') => continue,
+                        Some(')') => break,
+                        _ => return Err(AssembleError::BadPattern(name)),
+                    }
+                }
+            }
+            parser::Pattern::Constructor(name, args)
+        }
+        other => return Err(AssembleError::BadPattern(other.to_string())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_bytecode() -> Bytecode {
+        let mut bytecode = Bytecode::new();
+        bytecode.symbols = vec!["foo".to_string(), "bar".to_string()];
+        bytecode.constants = vec![
+            Literal::Integer(42),
+            Literal::String("hi there".to_string()),
+            Literal::Single(1.5),
+        ];
+        bytecode.constructors = vec![1, 2, 3];
+        bytecode.chunks = vec![Chunk {
+            reference: vec![1, 2],
+            instructions: vec![OpCode::LoadConst(0), OpCode::Call(3)],
+        }];
+        bytecode.instructions = vec![
+            OpCode::LoadSym(1),
+            OpCode::Builtin(5, 1),
+            OpCode::Def(0, 12),
+        ];
+        bytecode.matches = vec![Match {
+            expression: vec![OpCode::LoadConst(1)],
+            patterns: vec![
+                Pattern { pat: parser::Pattern::Wildcard, to_exec: vec![OpCode::Tuple(2)] },
+                Pattern {
+                    pat: parser::Pattern::Constructor(
+                        "Some".to_string(),
+                        vec![parser::Pattern::Binding("x".to_string())],
+                    ),
+                    to_exec: vec![OpCode::LoadSym(0)],
+                },
+            ],
+        }];
+        bytecode
+    }
+
+    #[test]
+    fn deserialize_is_inverse_of_serialize() {
+        let bytecode = sample_bytecode();
+        let round_tripped = Bytecode::deserialize(&bytecode.serialize()).expect("valid image");
+        assert_eq!(format!("{round_tripped:?}"), format!("{bytecode:?}"));
+    }
+
+    #[test]
+    fn serialize_container_round_trips_plaintext() {
+        let bytecode = sample_bytecode();
+        let container = bytecode.serialize_container(Algorithm::Plaintext, None).expect("plaintext needs no passphrase");
+        let round_tripped = Bytecode::deserialize_container(&container, None).expect("valid container");
+        assert_eq!(format!("{round_tripped:?}"), format!("{bytecode:?}"));
+    }
+
+    #[test]
+    fn serialize_container_round_trips_encrypted() {
+        for algorithm in [Algorithm::Aes256Gcm, Algorithm::ChaCha20Poly1305] {
+            let bytecode = sample_bytecode();
+            let container = bytecode
+                .serialize_container(algorithm, Some("correct horse battery staple"))
+                .expect("passphrase was given");
+            let round_tripped = Bytecode::deserialize_container(&container, Some("correct horse battery staple"))
+                .expect("valid container");
+            assert_eq!(format!("{round_tripped:?}"), format!("{bytecode:?}"));
+        }
+    }
+
+    #[test]
+    fn deserialize_container_rejects_wrong_passphrase() {
+        let bytecode = sample_bytecode();
+        let container = bytecode
+            .serialize_container(Algorithm::Aes256Gcm, Some("correct horse battery staple"))
+            .expect("passphrase was given");
+        let err = Bytecode::deserialize_container(&container, Some("wrong passphrase")).expect_err("should fail");
+        assert!(matches!(err, DeserializeError::DecryptionFailed));
+    }
+
+    #[test]
+    fn serialize_container_requires_passphrase_for_encryption() {
+        let bytecode = sample_bytecode();
+        let err = bytecode.serialize_container(Algorithm::Aes256Gcm, None).expect_err("should fail");
+        assert!(matches!(err, DeserializeError::MissingPassphrase));
+    }
+
+    #[test]
+    fn deserialize_container_rejects_unknown_algorithm_tag() {
+        let bytecode = sample_bytecode();
+        let mut container = bytecode.serialize_container(Algorithm::Plaintext, None).expect("plaintext needs no passphrase");
+        container[MAGIC.len() + 1] = 99;
+        let err = Bytecode::deserialize_container(&container, None).expect_err("should fail");
+        assert!(matches!(err, DeserializeError::UnknownAlgorithm(99)));
+    }
+
+    #[test]
+    fn deserialize_container_rejects_unsupported_version() {
+        let bytecode = sample_bytecode();
+        let mut container = bytecode.serialize_container(Algorithm::Plaintext, None).expect("plaintext needs no passphrase");
+        container[MAGIC.len()] = 99;
+        let err = Bytecode::deserialize_container(&container, None).expect_err("should fail");
+        assert!(matches!(err, DeserializeError::UnsupportedVersion(99)));
+    }
+
+    #[test]
+    fn assemble_is_inverse_of_disassemble() {
+        let bytecode = sample_bytecode();
+        let reassembled = Bytecode::assemble(&bytecode.disassemble()).expect("valid listing");
+        assert_eq!(format!("{reassembled:?}"), format!("{bytecode:?}"));
+    }
+
+    #[test]
+    fn disassemble_disambiguates_duplicate_symbol_names() {
+        let mut bytecode = Bytecode::new();
+        bytecode.symbols = vec!["x".to_string(), "x".to_string()];
+        bytecode.instructions = vec![OpCode::LoadSym(0), OpCode::LoadSym(1)];
+        let reassembled = Bytecode::assemble(&bytecode.disassemble()).expect("valid listing");
+        assert_eq!(format!("{reassembled:?}"), format!("{bytecode:?}"));
+    }
+
+    #[test]
+    fn assemble_round_trips_a_match_with_no_expression_or_patterns() {
+        let mut bytecode = Bytecode::new();
+        bytecode.matches = vec![Match { expression: vec![], patterns: vec![] }];
+        let reassembled = Bytecode::assemble(&bytecode.disassemble()).expect("valid listing");
+        assert_eq!(format!("{reassembled:?}"), format!("{bytecode:?}"));
+    }
+
+    #[test]
+    fn assemble_rejects_a_gap_in_symbol_ids() {
+        let text = "sym 0 a\nsym 2 c\nop load_sym 2\n";
+        let err = Bytecode::assemble(text).expect_err("gap in sym ids should fail");
+        assert!(matches!(err, AssembleError::MissingId("sym", 1)));
+    }
+
+    #[test]
+    fn assemble_rejects_a_gap_in_constant_ids() {
+        let text = "const 0 int 42\nconst 2 int 43\n";
+        let err = Bytecode::assemble(text).expect_err("gap in const ids should fail");
+        assert!(matches!(err, AssembleError::MissingId("const", 1)));
     }
 }